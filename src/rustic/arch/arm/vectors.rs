@@ -0,0 +1,190 @@
+/*
+ * Copyright (c) 2014 Matthew Iselin
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+//! The ARM exception vector table and the trampolines each entry jumps
+//! through on the way into `TrapHandler::trap`.
+//!
+//! Each vector is a `ldr pc, [pc, #24]` loading its real handler address
+//! out of a literal pool placed immediately after the eight vector words
+//! -- the offset is the same for every entry because the table is a
+//! fixed eight words and ARM fetches two instructions ahead of the one
+//! executing. `install` copies both the opcodes and the literal pool
+//! onto the low vectors at 0x00000000, which is where the CPU looks for
+//! them out of reset.
+//!
+//! Every trampoline below saves the banked registers and SPSR of the
+//! mode it was entered in onto that mode's own stack (`srsdb`), switches
+//! to System mode to run on the kernel's normal stack, calls `dispatch`
+//! with a trap number distinct per exception class, switches back, and
+//! returns with `rfeia`, which restores both PC and CPSR atomically.
+
+pub static UNDEFINED: uint = 0;
+pub static SOFTWARE_INTERRUPT: uint = 1;
+pub static PREFETCH_ABORT: uint = 2;
+pub static DATA_ABORT: uint = 3;
+pub static IRQ: uint = 4;
+pub static FIQ: uint = 5;
+
+static NUM_TRAPS: uint = 6;
+
+static mut trap_handlers: [Option<extern "Rust" fn(uint)>, ..6] = [None, ..6];
+
+static VECTOR_BASE: uint = 0x00000000;
+
+// `ldr pc, [pc, #24]`, identical for all eight vectors.
+static LDR_PC_PC_24: u32 = 0xE59FF018;
+
+static VECTOR_OPCODES: [u32, ..8] = [
+    LDR_PC_PC_24, LDR_PC_PC_24, LDR_PC_PC_24, LDR_PC_PC_24,
+    LDR_PC_PC_24, LDR_PC_PC_24, LDR_PC_PC_24, LDR_PC_PC_24,
+];
+
+/// Write the vector table and its literal pool into place. Must run
+/// before interrupts are ever enabled.
+pub fn install() {
+    let targets: [uint, ..8] = [
+        reset_stub as uint,
+        undefined_stub as uint,
+        swi_stub as uint,
+        prefetch_abort_stub as uint,
+        data_abort_stub as uint,
+        0, // reserved vector, never taken
+        irq_stub as uint,
+        fiq_stub as uint,
+    ];
+
+    unsafe {
+        let vectors = VECTOR_BASE as *mut u32;
+        for i in range(0u, 8) {
+            ::std::ptr::write(vectors.offset(i as int), VECTOR_OPCODES[i]);
+        }
+
+        let literal_pool = (VECTOR_BASE + 8 * 4) as *mut uint;
+        for i in range(0u, 8) {
+            ::std::ptr::write(literal_pool.offset(i as int), targets[i]);
+        }
+    }
+}
+
+pub fn register_trap(num: uint, handler: extern "Rust" fn(uint)) {
+    if num < NUM_TRAPS {
+        unsafe { trap_handlers[num] = Some(handler) };
+    }
+}
+
+#[no_mangle]
+extern "C" fn dispatch(num: uint) {
+    let handler = unsafe { trap_handlers[num] };
+    match handler {
+        Some(f) => f(num),
+        None => (),
+    }
+}
+
+#[naked]
+unsafe extern "C" fn reset_stub() {
+    // Not expected to be taken once the kernel is running; loop rather
+    // than fall through into whatever garbage follows the vector table.
+    asm!("1: b 1b" ::: "memory" : "volatile");
+}
+
+#[naked]
+unsafe extern "C" fn undefined_stub() {
+    asm!("
+        srsdb sp!, #0x1B
+        push {r0-r3, r12, lr}
+        mov r0, #0
+        cps #0x1F
+        bl dispatch
+        cps #0x1B
+        pop {r0-r3, r12, lr}
+        rfeia sp!
+    " ::: "memory" : "volatile");
+}
+
+#[naked]
+unsafe extern "C" fn swi_stub() {
+    asm!("
+        srsdb sp!, #0x13
+        push {r0-r3, r12, lr}
+        mov r0, #1
+        cps #0x1F
+        bl dispatch
+        cps #0x13
+        pop {r0-r3, r12, lr}
+        rfeia sp!
+    " ::: "memory" : "volatile");
+}
+
+#[naked]
+unsafe extern "C" fn prefetch_abort_stub() {
+    asm!("
+        sub lr, lr, #4
+        srsdb sp!, #0x17
+        push {r0-r3, r12, lr}
+        mov r0, #2
+        cps #0x1F
+        bl dispatch
+        cps #0x17
+        pop {r0-r3, r12, lr}
+        rfeia sp!
+    " ::: "memory" : "volatile");
+}
+
+#[naked]
+unsafe extern "C" fn data_abort_stub() {
+    asm!("
+        sub lr, lr, #8
+        srsdb sp!, #0x17
+        push {r0-r3, r12, lr}
+        mov r0, #3
+        cps #0x1F
+        bl dispatch
+        cps #0x17
+        pop {r0-r3, r12, lr}
+        rfeia sp!
+    " ::: "memory" : "volatile");
+}
+
+#[naked]
+unsafe extern "C" fn irq_stub() {
+    asm!("
+        sub lr, lr, #4
+        srsdb sp!, #0x12
+        push {r0-r3, r12, lr}
+        mov r0, #4
+        cps #0x1F
+        bl dispatch
+        cps #0x12
+        pop {r0-r3, r12, lr}
+        rfeia sp!
+    " ::: "memory" : "volatile");
+}
+
+#[naked]
+unsafe extern "C" fn fiq_stub() {
+    asm!("
+        sub lr, lr, #4
+        srsdb sp!, #0x11
+        push {r0-r3, r12, lr}
+        mov r0, #5
+        cps #0x1F
+        bl dispatch
+        cps #0x11
+        pop {r0-r3, r12, lr}
+        rfeia sp!
+    " ::: "memory" : "volatile");
+}
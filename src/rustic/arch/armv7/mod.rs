@@ -0,0 +1,57 @@
+/*
+ * Copyright (c) 2014 Matthew Iselin
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+use arch::arm::vectors;
+use arch::{Architecture, ArchitectureState};
+
+static CPSR_I: uint = 1 << 7; // IRQ mask
+static CPSR_F: uint = 1 << 6; // FIQ mask
+
+fn read_cpsr() -> uint {
+    let cpsr: uint;
+    unsafe { asm!("mrs $0, cpsr" : "=r"(cpsr)) };
+    cpsr
+}
+
+impl Architecture for ArchitectureState {
+    fn initialise(&mut self) -> bool {
+        vectors::install();
+        self.initialised = true;
+        self.initialised
+    }
+
+    fn register_trap(&mut self, num: uint, handler: extern "Rust" fn(uint)) {
+        vectors::register_trap(num, handler);
+    }
+
+    fn get_interrupts(&self) -> bool {
+        (read_cpsr() & (CPSR_I | CPSR_F)) == 0
+    }
+
+    fn set_interrupts(&mut self, enable: bool) {
+        unsafe {
+            if enable {
+                asm!("cpsie if" :::: "volatile");
+            } else {
+                asm!("cpsid if" :::: "volatile");
+            }
+        }
+    }
+
+    fn wait_for_event(&self) {
+        unsafe { asm!("wfi" :::: "volatile") };
+    }
+}
@@ -0,0 +1,227 @@
+/*
+ * Copyright (c) 2014 Matthew Iselin
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+//! Classic two-level i386 paging, using a recursive mapping so the active
+//! page directory and its page tables are always reachable by virtual
+//! address without a temporary mapping.
+//!
+//! The last page directory entry (1023) points back at the directory
+//! itself, which puts the directory at 0xFFFFF000 and page table `n` at
+//! 0xFFC00000 + (n << 12).
+
+use std;
+
+use arch::paging::{MemoryManager, PAGE_PRESENT, PAGE_WRITABLE};
+
+static ENTRIES_PER_TABLE: uint = 1024;
+static RECURSIVE_SLOT: uint = 1023;
+
+// A second directory slot, borrowed only for the few instructions it takes
+// to initialise a fresh, not-yet-active directory (see `with_scratch_map`).
+// Using a fixed slot rather than the recursive one means this never fights
+// with whatever the active directory's own entry 1023 is doing.
+static SCRATCH_SLOT: uint = 1022;
+
+// pde_index(0xC0000000): every address space shares the kernel's mappings
+// from the 3GB split upward, so a freshly created one still sees the
+// kernel when it is later switched to.
+static KERNEL_PDE_START: uint = 768;
+
+static PAGE_DIRECTORY_VIRT: uint = 0xFFFFF000;
+
+fn page_table_virt(pde_index: uint) -> uint {
+    0xFFC00000 + (pde_index << 12)
+}
+
+fn pde_index(virt: uint) -> uint { virt >> 22 }
+fn pte_index(virt: uint) -> uint { (virt >> 12) & 0x3FF }
+
+unsafe fn invlpg(virt: uint) {
+    asm!("invlpg ($0)" :: "r"(virt) : "memory");
+}
+
+// Early-boot physical frame allocator: good enough to bootstrap page
+// tables before the real physical memory manager exists. Every frame it
+// hands out is permanent for the life of the kernel.
+static mut next_free_frame: uint = 0;
+
+unsafe fn alloc_frame() -> uint {
+    // Fixed base: frames are handed out upward from 4MB. Nothing else sets
+    // `next_free_frame`, and frames are never checked against the memory
+    // map or the kernel image.
+    if next_free_frame == 0 {
+        next_free_frame = 0x400000;
+    }
+    let frame = next_free_frame;
+    next_free_frame += 0x1000;
+    frame
+}
+
+fn current_directory_phys() -> uint {
+    let cr3: uint;
+    unsafe { asm!("mov %cr3, $0" : "=r"(cr3)) };
+    cr3 & !0xFFFu
+}
+
+/// Temporarily map `phys` into the *active* directory's `SCRATCH_SLOT` and
+/// hand the caller the virtual address it landed at, so a directory that
+/// isn't loaded in CR3 yet can still be initialised. The mapping is torn
+/// down again before returning.
+///
+/// This only ever touches the scratch slot of whichever directory is
+/// currently active -- it must not be used on the directory that is itself
+/// active, since installing and removing the scratch mapping would stomp
+/// on its own entry 1022.
+unsafe fn with_scratch_map<T>(phys: uint, f: |uint| -> T) -> T {
+    let active = AddressSpace{directory_phys: current_directory_phys()};
+    active.set_directory_entry(SCRATCH_SLOT, (phys as u32) | PAGE_PRESENT as u32 | PAGE_WRITABLE as u32);
+    invlpg(page_table_virt(SCRATCH_SLOT));
+
+    let result = f(page_table_virt(SCRATCH_SLOT));
+
+    active.set_directory_entry(SCRATCH_SLOT, 0);
+    invlpg(page_table_virt(SCRATCH_SLOT));
+
+    result
+}
+
+/// An i386 address space: just the physical address of its page
+/// directory. The directory's own contents are reached recursively, so
+/// nothing else needs to be stored here.
+///
+/// Every accessor below (`directory_entry`, `map`, `unmap`, `translate`,
+/// ...) reaches its target through the recursive mapping, which always
+/// reflects whichever directory CR3 currently points at -- they are only
+/// valid to call on `self` when `self` is the address space presently
+/// loaded. Call `switch_to` first if it is not.
+pub struct AddressSpace {
+    directory_phys: uint,
+}
+
+impl AddressSpace {
+    /// Load this address space into CR3, making it the one the recursive
+    /// mapping and every `MemoryManager` method below operate on.
+    pub fn switch_to(&self) {
+        unsafe { asm!("mov $0, %cr3" :: "r"(self.directory_phys) : "memory" : "volatile") };
+    }
+
+    fn directory_entry(&self, index: uint) -> u32 {
+        unsafe { std::ptr::read((PAGE_DIRECTORY_VIRT + index * 4) as *const u32) }
+    }
+
+    fn set_directory_entry(&self, index: uint, val: u32) {
+        unsafe { std::ptr::write((PAGE_DIRECTORY_VIRT + index * 4) as *mut u32, val) };
+    }
+
+    fn table_entry(&self, pde: uint, pte: uint) -> u32 {
+        unsafe { std::ptr::read((page_table_virt(pde) + pte * 4) as *const u32) }
+    }
+
+    fn set_table_entry(&self, pde: uint, pte: uint, val: u32) {
+        unsafe { std::ptr::write((page_table_virt(pde) + pte * 4) as *mut u32, val) };
+    }
+
+    /// Ensure a page table exists for `pde`, allocating and zeroing one
+    /// on demand.
+    fn ensure_table(&mut self, pde: uint, flags: uint) {
+        let entry = self.directory_entry(pde);
+        if entry & PAGE_PRESENT as u32 == 0 {
+            let frame = unsafe { alloc_frame() };
+            self.set_directory_entry(pde, (frame as u32) | (flags as u32) | PAGE_PRESENT as u32 | PAGE_WRITABLE as u32);
+
+            // The table is now reachable at its recursive address; zero it
+            // before anything reads stale physical memory through it.
+            for i in range(0u, ENTRIES_PER_TABLE) {
+                self.set_table_entry(pde, i, 0);
+            }
+        }
+    }
+}
+
+impl MemoryManager for AddressSpace {
+    fn new_address_space() -> AddressSpace {
+        let directory_phys = unsafe { alloc_frame() };
+
+        // `self.directory_phys` only matters to `switch_to` -- every other
+        // method reaches its directory through the fixed recursive
+        // address, which tracks CR3, not `self`. So a brand new, not-yet-
+        // active directory has to be built through a temporary scratch
+        // mapping of the *active* directory instead of through its own
+        // (not yet meaningful) recursive accessors.
+        unsafe {
+            with_scratch_map(directory_phys, |window| {
+                for i in range(0u, ENTRIES_PER_TABLE) {
+                    std::ptr::write((window + i * 4) as *mut u32, 0);
+                }
+
+                // Self-map the new directory's own recursive slot.
+                std::ptr::write((window + RECURSIVE_SLOT * 4) as *mut u32,
+                    (directory_phys as u32) | PAGE_PRESENT as u32 | PAGE_WRITABLE as u32);
+
+                // Share the kernel's own high-half mappings so the kernel
+                // is still reachable once something switches to this
+                // address space. SCRATCH_SLOT is excluded: the active
+                // directory's entry at that index is this very transient
+                // mapping, not a real kernel mapping, and copying it in
+                // would leave the new directory's SCRATCH_SLOT entry
+                // pointing at its own directory frame.
+                let active = AddressSpace{directory_phys: current_directory_phys()};
+                for i in range(KERNEL_PDE_START, SCRATCH_SLOT) {
+                    let entry = active.directory_entry(i);
+                    std::ptr::write((window + i * 4) as *mut u32, entry);
+                }
+            });
+        }
+
+        AddressSpace{directory_phys: directory_phys}
+    }
+
+    fn map(&mut self, virt: uint, phys: uint, flags: uint) {
+        let pde = pde_index(virt);
+        let pte = pte_index(virt);
+
+        self.ensure_table(pde, flags);
+        self.set_table_entry(pde, pte, (phys as u32) | (flags as u32) | PAGE_PRESENT as u32);
+
+        unsafe { invlpg(virt) };
+    }
+
+    fn unmap(&mut self, virt: uint) {
+        let pde = pde_index(virt);
+        let pte = pte_index(virt);
+
+        if self.directory_entry(pde) & PAGE_PRESENT as u32 != 0 {
+            self.set_table_entry(pde, pte, 0);
+            unsafe { invlpg(virt) };
+        }
+    }
+
+    fn translate(&self, virt: uint) -> Option<uint> {
+        let pde = pde_index(virt);
+        let pte = pte_index(virt);
+
+        if self.directory_entry(pde) & PAGE_PRESENT as u32 == 0 {
+            return None;
+        }
+
+        let entry = self.table_entry(pde, pte);
+        if entry & PAGE_PRESENT as u32 == 0 {
+            return None;
+        }
+
+        Some((entry & !0xFFFu32) as uint | (virt & 0xFFF))
+    }
+}
@@ -0,0 +1,228 @@
+/*
+ * Copyright (c) 2014 Matthew Iselin
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+//! A plain round-robin scheduler for i386.
+//!
+//! `spawn` allocates a stack and a thread control block and enqueues it;
+//! `reschedule` swaps to the next runnable thread via `switch_context`.
+//! `timer_tick` calls `reschedule` and has the shape of a
+//! `TimerHandlers::register_timer` callback, but nothing here registers
+//! it, so threads only switch when `reschedule` is called; `terminate`
+//! marks the current thread dead and reschedules away from it for good.
+//! Dead TCBs (and the stacks they own) are reaped lazily, on whichever
+//! later `reschedule` first runs on a different thread -- see
+//! `reap_dead`.
+
+use std::mem;
+use std::ptr;
+
+use super::state::State;
+
+static STACK_SIZE: uint = 16 * 1024;
+
+enum ThreadStatus {
+    Runnable,
+    Dead,
+}
+
+struct Tcb {
+    state: State,
+    // Kept alive for the lifetime of the thread purely so its backing
+    // memory isn't freed out from under `state.esp`; never read directly.
+    _stack: Vec<u8>,
+    status: ThreadStatus,
+}
+
+struct Scheduler {
+    threads: Vec<Box<Tcb>>,
+    current: uint,
+}
+
+static mut scheduler_ptr: *mut Scheduler = 0 as *mut Scheduler;
+
+fn scheduler() -> &'static mut Scheduler {
+    unsafe {
+        if scheduler_ptr.is_null() {
+            let boxed = box Scheduler{threads: Vec::new(), current: 0};
+            scheduler_ptr = mem::transmute(boxed);
+
+            // Thread 0 is whatever was already running when the scheduler
+            // was first touched (the boot thread); it owns no stack of its
+            // own here since it is already running on one.
+            let boot = box Tcb{state: State::new(), _stack: Vec::new(), status: Runnable};
+            (*scheduler_ptr).threads.push(boot);
+        }
+        &mut *scheduler_ptr
+    }
+}
+
+unsafe fn push(sp: &mut uint, val: uint) {
+    *sp -= mem::size_of::<uint>();
+    ptr::write(*sp as *mut uint, val);
+}
+
+/// Spawn `p` as a new runnable thread. Its initial stack frame is built so
+/// that the first `switch_context` into it falls straight into
+/// `thread_entry`, which runs `p` to completion and then calls
+/// `terminate` -- the thread never returns in the ordinary sense.
+pub fn spawn(p: proc()) {
+    let mut stack = Vec::from_elem(STACK_SIZE, 0u8);
+    let mut sp = stack.as_mut_ptr() as uint + STACK_SIZE;
+
+    let boxed: Box<proc()> = box p;
+    let arg = unsafe { mem::transmute::<Box<proc()>, uint>(boxed) };
+
+    unsafe {
+        // switch_context's `ret` jumps into thread_entry the same way a
+        // `call thread_entry` would have, except the `call` itself never
+        // happened -- so thread_entry's own prologue still expects a
+        // return-address word below its first argument. Without it, `arg`
+        // would land where thread_entry looks for its return address, and
+        // thread_entry would read the real argument one word off.
+        // thread_entry never returns, so that placeholder is never used
+        // for anything but occupying the slot.
+        push(&mut sp, arg);
+        push(&mut sp, 0); // placeholder return address for thread_entry's frame
+        push(&mut sp, thread_entry as uint);
+
+        // switch_context's epilogue pops these before the `ret` above.
+        push(&mut sp, 0); // ebp
+        push(&mut sp, 0); // edi
+        push(&mut sp, 0); // esi
+        push(&mut sp, 0); // ebx
+        push(&mut sp, 0); // eflags
+    }
+
+    let tcb = box Tcb{state: State{esp: sp}, _stack: stack, status: Runnable};
+    scheduler().threads.push(tcb);
+}
+
+extern "C" fn thread_entry(arg: uint) -> ! {
+    let boxed: Box<proc()> = unsafe { mem::transmute(arg) };
+    (*boxed)();
+    terminate()
+}
+
+/// Mark the running thread dead and switch away from it for the last
+/// time. Its `Tcb` (stack included) is not freed here -- this function is
+/// still running on that very stack, so dropping it out from under the
+/// CPU mid-switch would be fatal. `reschedule` reaps it later, once
+/// something else is running.
+pub fn terminate() -> ! {
+    {
+        let s = scheduler();
+        let current = s.current;
+        s.threads.get_mut(current).status = Dead;
+    }
+    reschedule();
+
+    // reschedule() only returns here if every other thread was already
+    // Dead, so there was nothing left to switch to and this thread is
+    // never coming back. Halt rather than run off the end of a dead
+    // thread's stack.
+    loop {
+        unsafe { asm!("hlt" :::: "volatile") };
+    }
+}
+
+/// Drop every `Dead` TCB from the run queue except the one currently
+/// executing. The current thread's stack is exactly what this function
+/// itself may be running on (immediately after it called `terminate`), so
+/// freeing it here would pull the stack out from under the CPU; a thread
+/// that terminates is instead reaped on some *later* reschedule, once a
+/// different thread is the one running.
+fn reap_dead(s: &mut Scheduler) {
+    let mut i = 0u;
+    while i < s.threads.len() {
+        let dead = match s.threads.get(i).status { Dead => true, Runnable => false };
+        if dead && i != s.current {
+            let last = s.threads.len() - 1;
+            s.threads.swap_remove(i);
+            if s.current == last {
+                s.current = i;
+            }
+        } else {
+            i += 1;
+        }
+    }
+}
+
+/// Save the current thread's context and switch to the next runnable one,
+/// round-robin. A no-op when fewer than two threads exist.
+pub fn reschedule() {
+    let s = scheduler();
+    reap_dead(s);
+
+    let count = s.threads.len();
+    if count <= 1 {
+        return;
+    }
+
+    let prev = s.current;
+    let mut next = (prev + 1) % count;
+    loop {
+        match s.threads.get(next).status {
+            Runnable => break,
+            Dead => {
+                if next == prev {
+                    // Nothing left to run.
+                    return;
+                }
+                next = (next + 1) % count;
+            }
+        }
+    }
+
+    s.current = next;
+
+    let old: *mut State = &mut s.threads.get_mut(prev).state;
+    let new: *const State = &s.threads.get(next).state;
+    unsafe { switch_context(old, new) };
+}
+
+/// A `TimerHandlers` callback that reschedules on every timer tick. It is
+/// not registered by this module.
+pub extern "Rust" fn timer_tick(_ms: uint) {
+    reschedule();
+}
+
+/// Save the callee-saved registers and flags of the outgoing thread onto
+/// its own stack, record where that left the stack pointer in `*old`,
+/// then do the reverse for the incoming thread from `*new` and return
+/// into wherever its stack says to.
+#[naked]
+unsafe extern "C" fn switch_context(old: *mut State, new: *const State) {
+    asm!("
+        pushl %ebp
+        pushl %edi
+        pushl %esi
+        pushl %ebx
+        pushfl
+
+        movl 24(%esp), %eax
+        movl %esp, (%eax)
+
+        movl 28(%esp), %eax
+        movl (%eax), %esp
+
+        popfl
+        popl %ebx
+        popl %esi
+        popl %edi
+        popl %ebp
+        ret
+    " ::: "eax" : "volatile");
+}
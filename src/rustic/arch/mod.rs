@@ -17,6 +17,10 @@
 #[cfg(arch_i386)]
 mod i386;
 
+// Shared ARM exception-vector handling pulled in by both armv6 and armv7.
+#[cfg(any(arch_armv6, arch_armv7))]
+mod arm;
+
 #[cfg(arch_armv6)]
 mod armv6;
 
@@ -26,6 +30,12 @@ mod armv7;
 // State module pulls in architecture-specific state type as 'State' type.
 mod state;
 
+// Paging module defines the arch-generic MemoryManager contract; each
+// architecture provides its own AddressSpace type implementing it
+// (see e.g. i386::paging::AddressSpace).
+mod paging;
+pub use self::paging::{MemoryManager, PAGE_PRESENT, PAGE_WRITABLE, PAGE_USER, PAGE_GLOBAL};
+
 pub trait Architecture {
     fn initialise(&mut self) -> bool;
 
@@ -0,0 +1,44 @@
+/*
+ * Copyright (c) 2014 Matthew Iselin
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+//! Arch-generic side of the virtual memory subsystem. Each architecture
+//! provides its own address space type implementing `MemoryManager`; this
+//! module just pins down the shared vocabulary (flags, trait contract) so
+//! arch-independent kernel code never has to know whether it is running
+//! on a two-level x86 directory or an ARM long-descriptor table.
+
+pub static PAGE_PRESENT: uint = 1 << 0;
+pub static PAGE_WRITABLE: uint = 1 << 1;
+pub static PAGE_USER: uint = 1 << 2;
+pub static PAGE_GLOBAL: uint = 1 << 8;
+
+/// A single address space. `new_address_space` is how the kernel obtains
+/// one in the first place (e.g. for a freshly-spawned process); `map`,
+/// `unmap`, and `translate` then manage its mappings.
+pub trait MemoryManager {
+    /// Create a fresh, empty address space (kernel mappings aside).
+    fn new_address_space() -> Self;
+
+    /// Map `virt` to `phys`, allocating any intermediate page tables that
+    /// do not yet exist. `flags` is an OR of the `PAGE_*` constants above.
+    fn map(&mut self, virt: uint, phys: uint, flags: uint);
+
+    /// Remove the mapping for `virt`, if any.
+    fn unmap(&mut self, virt: uint);
+
+    /// Resolve `virt` to its mapped physical address, if it is mapped.
+    fn translate(&self, virt: uint) -> Option<uint>;
+}
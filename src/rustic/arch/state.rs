@@ -0,0 +1,37 @@
+/*
+ * Copyright (c) 2014 Matthew Iselin
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+//! Pulls in the architecture-specific register-snapshot type as `State`,
+//! so the rest of `arch` can talk about "a saved context" without caring
+//! which CPU it is running on.
+
+#[cfg(arch_i386)]
+pub use self::i386::State;
+
+#[cfg(arch_armv6)]
+pub use self::armv6::State;
+
+#[cfg(arch_armv7)]
+pub use self::armv7::State;
+
+#[cfg(arch_i386)]
+use super::i386::state as i386;
+
+#[cfg(arch_armv6)]
+use super::armv6::state as armv6;
+
+#[cfg(arch_armv7)]
+use super::armv7::state as armv7;
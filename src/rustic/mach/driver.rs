@@ -0,0 +1,102 @@
+/*
+ * Copyright (c) 2014 Matthew Iselin
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+//! A small probe/attach driver framework: a platform's `initialise`
+//! registers built-in drivers, enumerates and binds, and then constructs
+//! each device whose driver bound. A `DeviceDescriptor`
+//! describes a slice of resources (an IO port range, an MMIO window, an
+//! IRQ line) discovered by whatever enumeration the platform has on
+//! offer -- ACPI tables for some, fixed legacy addresses for others --
+//! and `DeviceManager` matches descriptors against registered drivers.
+
+/// The resources a candidate device occupies, as handed to a driver's
+/// `probe`/`attach`.
+pub struct DeviceDescriptor {
+    pub name: &'static str,
+    pub io_ports: Option<(u16, u16)>,
+    pub mmio: Option<(uint, uint)>,
+    pub irq: Option<uint>,
+}
+
+pub enum DriverError {
+    ResourceBusy,
+    ResourceMissing,
+    InitFailed,
+}
+
+/// A driver template: registered once, `probe`d against every descriptor
+/// the platform enumerates, and `attach`ed to the ones it claims, purely to
+/// decide whether the device is present (see `DeviceManager::is_bound`).
+/// `Box<Driver>` is not downcastable back to its concrete type in this
+/// Rust, so `attach`'s result is not a substitute for constructing and
+/// using the concrete device type directly -- platforms still do that
+/// themselves once presence is confirmed.
+pub trait Driver {
+    fn name(&self) -> &'static str;
+
+    fn probe(&self, desc: &DeviceDescriptor) -> bool;
+
+    fn attach(&mut self, desc: &DeviceDescriptor) -> Result<Box<Driver>, DriverError>;
+}
+
+/// Holds the descriptors a platform has enumerated and the drivers
+/// available to bind them, and matches the two up.
+pub struct DeviceManager {
+    descriptors: Vec<DeviceDescriptor>,
+    templates: Vec<Box<Driver>>,
+    bound: Vec<Box<Driver>>,
+}
+
+impl DeviceManager {
+    pub fn new() -> DeviceManager {
+        DeviceManager{descriptors: Vec::new(), templates: Vec::new(), bound: Vec::new()}
+    }
+
+    pub fn add_descriptor(&mut self, desc: DeviceDescriptor) {
+        self.descriptors.push(desc);
+    }
+
+    pub fn register_driver(&mut self, driver: Box<Driver>) {
+        self.templates.push(driver);
+    }
+
+    /// Match every descriptor against the registered drivers in order,
+    /// attaching the first one whose `probe` accepts it. Unmatched
+    /// descriptors are silently left unbound and are not reported.
+    pub fn enumerate_and_bind(&mut self) {
+        for desc in self.descriptors.iter() {
+            for template in self.templates.mut_iter() {
+                if !template.probe(desc) {
+                    continue;
+                }
+
+                match template.attach(desc) {
+                    Ok(bound) => {
+                        self.bound.push(bound);
+                        break;
+                    }
+                    Err(_) => continue,
+                }
+            }
+        }
+    }
+
+    /// Whether a driver with this name successfully bound to one of the
+    /// enumerated descriptors.
+    pub fn is_bound(&self, name: &str) -> bool {
+        self.bound.iter().any(|d| d.name() == name)
+    }
+}
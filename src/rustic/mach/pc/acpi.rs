@@ -0,0 +1,224 @@
+/*
+ * Copyright (c) 2014 Matthew Iselin
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+//! Minimal ACPI table walker, just enough to find the MADT and pull the
+//! Local APIC / IO APIC / interrupt-source-override entries out of it.
+//! Everything here runs against identity-mapped physical memory, which
+//! holds during early boot before paging is set up.
+
+use std;
+
+static RSDP_SIGNATURE: [u8, ..8] = [b'R', b'S', b'D', b' ', b'P', b'T', b'R', b' '];
+static MADT_SIGNATURE: [u8, ..4] = [b'A', b'P', b'I', b'C'];
+
+static BIOS_SCAN_START: uint = 0xE0000;
+static BIOS_SCAN_END: uint = 0xFFFFF;
+
+#[repr(C, packed)]
+struct Rsdp {
+    signature: [u8, ..8],
+    checksum: u8,
+    oem_id: [u8, ..6],
+    revision: u8,
+    rsdt_address: u32,
+    // ACPI 2.0+ fields; only valid when revision >= 2.
+    length: u32,
+    xsdt_address: u64,
+    extended_checksum: u8,
+    reserved: [u8, ..3],
+}
+
+#[repr(C, packed)]
+struct SdtHeader {
+    signature: [u8, ..4],
+    length: u32,
+    revision: u8,
+    checksum: u8,
+    oem_id: [u8, ..6],
+    oem_table_id: [u8, ..8],
+    oem_revision: u32,
+    creator_id: u32,
+    creator_revision: u32,
+}
+
+/// A parsed Local APIC entry (MADT type 0).
+pub struct LocalApic {
+    pub processor_id: u8,
+    pub apic_id: u8,
+    pub enabled: bool,
+}
+
+/// A parsed IO APIC entry (MADT type 1).
+pub struct IoApicInfo {
+    pub id: u8,
+    pub address: uint,
+    pub gsi_base: u32,
+}
+
+/// A parsed interrupt-source-override entry (MADT type 2), remapping a
+/// legacy ISA IRQ onto a different global system interrupt.
+pub struct InterruptOverride {
+    pub bus: u8,
+    pub source_irq: u8,
+    pub gsi: u32,
+    pub flags: u16,
+}
+
+/// Everything of interest pulled out of the MADT.
+pub struct MadtInfo {
+    pub local_apic_address: uint,
+    pub local_apics: Vec<LocalApic>,
+    pub io_apics: Vec<IoApicInfo>,
+    pub overrides: Vec<InterruptOverride>,
+}
+
+fn checksum_ok(base: uint, len: uint) -> bool {
+    let mut sum: u8 = 0;
+    for i in range(0u, len) {
+        sum += unsafe { std::ptr::read((base + i) as *const u8) };
+    }
+    sum == 0
+}
+
+unsafe fn bytes_at(addr: uint, len: uint) -> &'static [u8] {
+    std::mem::transmute(std::raw::Slice { data: addr as *const u8, len: len })
+}
+
+/// Scan the BIOS read-only memory region for the RSDP signature. The EBDA
+/// is not separately located here since most firmware mirrors the RSDP
+/// into the 0xE0000-0xFFFFF window as well; if that ever proves untrue in
+/// practice this can grow an EBDA-base lookup via the BDA at 0x40E.
+fn find_rsdp() -> Option<uint> {
+    let mut addr = BIOS_SCAN_START;
+    while addr < BIOS_SCAN_END {
+        let sig = unsafe { bytes_at(addr, 8) };
+        if sig == RSDP_SIGNATURE.as_slice() {
+            let len = if unsafe { std::ptr::read((addr + 15) as *const u8) } >= 2 {
+                unsafe { std::ptr::read((addr + 20) as *const u32) } as uint
+            } else {
+                20
+            };
+            if checksum_ok(addr, len) {
+                return Some(addr);
+            }
+        }
+        addr += 16;
+    }
+    None
+}
+
+fn sdt_entries(header_addr: uint, entry_size: uint) -> Vec<uint> {
+    let header: &SdtHeader = unsafe { std::mem::transmute(header_addr as *const SdtHeader) };
+    let table_len = header.length as uint;
+    let entry_count = (table_len - std::mem::size_of::<SdtHeader>()) / entry_size;
+    let entries_base = header_addr + std::mem::size_of::<SdtHeader>();
+
+    let mut out = Vec::with_capacity(entry_count);
+    for i in range(0u, entry_count) {
+        let ptr = entries_base + i * entry_size;
+        let addr = if entry_size == 8 {
+            unsafe { std::ptr::read(ptr as *const u64) as uint }
+        } else {
+            unsafe { std::ptr::read(ptr as *const u32) as uint }
+        };
+        out.push(addr);
+    }
+    out
+}
+
+fn parse_madt(madt_addr: uint) -> MadtInfo {
+    let header: &SdtHeader = unsafe { std::mem::transmute(madt_addr as *const SdtHeader) };
+    let local_apic_address = unsafe {
+        std::ptr::read((madt_addr + std::mem::size_of::<SdtHeader>()) as *const u32)
+    } as uint;
+
+    let mut info = MadtInfo{
+        local_apic_address: local_apic_address,
+        local_apics: Vec::new(),
+        io_apics: Vec::new(),
+        overrides: Vec::new(),
+    };
+
+    let records_base = madt_addr + std::mem::size_of::<SdtHeader>() + 8;
+    let records_end = madt_addr + header.length as uint;
+
+    let mut cursor = records_base;
+    while cursor < records_end {
+        let entry_type = unsafe { std::ptr::read(cursor as *const u8) };
+        let entry_length = unsafe { std::ptr::read((cursor + 1) as *const u8) } as uint;
+        if entry_length == 0 {
+            break;
+        }
+
+        match entry_type {
+            0 => {
+                let processor_id = unsafe { std::ptr::read((cursor + 2) as *const u8) };
+                let apic_id = unsafe { std::ptr::read((cursor + 3) as *const u8) };
+                let flags = unsafe { std::ptr::read((cursor + 4) as *const u32) };
+                info.local_apics.push(LocalApic{
+                    processor_id: processor_id,
+                    apic_id: apic_id,
+                    enabled: (flags & 1) != 0,
+                });
+            }
+            1 => {
+                let id = unsafe { std::ptr::read((cursor + 2) as *const u8) };
+                let address = unsafe { std::ptr::read((cursor + 4) as *const u32) } as uint;
+                let gsi_base = unsafe { std::ptr::read((cursor + 8) as *const u32) };
+                info.io_apics.push(IoApicInfo{id: id, address: address, gsi_base: gsi_base});
+            }
+            2 => {
+                let bus = unsafe { std::ptr::read((cursor + 2) as *const u8) };
+                let source_irq = unsafe { std::ptr::read((cursor + 3) as *const u8) };
+                let gsi = unsafe { std::ptr::read((cursor + 4) as *const u32) };
+                let flags = unsafe { std::ptr::read((cursor + 8) as *const u16) };
+                info.overrides.push(InterruptOverride{
+                    bus: bus, source_irq: source_irq, gsi: gsi, flags: flags,
+                });
+            }
+            _ => (),
+        }
+
+        cursor += entry_length;
+    }
+
+    info
+}
+
+/// Locate and parse the MADT, returning `None` when no ACPI tables are
+/// present at all (e.g. under emulators that only expose the 8259/8254).
+pub fn find_madt() -> Option<MadtInfo> {
+    let rsdp_addr = match find_rsdp() {
+        Some(addr) => addr,
+        None => return None,
+    };
+    let rsdp: &Rsdp = unsafe { std::mem::transmute(rsdp_addr as *const Rsdp) };
+
+    let (root_addr, entry_size) = if rsdp.revision >= 2 && rsdp.xsdt_address != 0 {
+        (rsdp.xsdt_address as uint, 8u)
+    } else {
+        (rsdp.rsdt_address as uint, 4u)
+    };
+
+    for entry_addr in sdt_entries(root_addr, entry_size).into_iter() {
+        let sig = unsafe { bytes_at(entry_addr, 4) };
+        if sig == MADT_SIGNATURE.as_slice() {
+            return Some(parse_madt(entry_addr));
+        }
+    }
+
+    None
+}
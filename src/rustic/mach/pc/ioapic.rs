@@ -0,0 +1,138 @@
+/*
+ * Copyright (c) 2014 Matthew Iselin
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+use std::cell::RefCell;
+use std::mem;
+use std::rc::Rc;
+
+use mach::IrqHandler;
+use mach::regs::{ReadWrite, redtbl};
+
+use super::acpi::IoApicInfo;
+
+static IOAPICID: u32 = 0x00;
+static IOAPICVER: u32 = 0x01;
+static IOREDTBL: u32 = 0x10;
+
+/// The IO APIC's MMIO register window: write the index you want to
+/// `ioregsel`, then read/write the selected register through `iowin`.
+/// Laying these out as typed registers (rather than raw offsets into the
+/// MMIO base) is what actually picks the right width and issues a
+/// genuinely volatile access for every field.
+#[repr(C)]
+struct IoApicRegs {
+    ioregsel: ReadWrite<u32>,
+    _reserved: [u32, ..3],
+    iowin: ReadWrite<u32>,
+}
+
+/// A single IO APIC, programmed through its MMIO register window. This
+/// replaces the 8259 as the `register_irq` backend whenever the MADT
+/// advertises one.
+pub struct IoApic {
+    base: uint,
+    gsi_base: u32,
+    num_entries: u32,
+    handlers: Vec<Option<Rc<RefCell<Box<IrqHandler>>>>>,
+}
+
+impl IoApic {
+    fn regs(&self) -> &mut IoApicRegs {
+        unsafe { mem::transmute(self.base as *mut IoApicRegs) }
+    }
+
+    fn read_reg(&self, index: u32) -> u32 {
+        let regs = self.regs();
+        regs.ioregsel.set(index);
+        regs.iowin.get()
+    }
+
+    fn write_reg(&self, index: u32, val: u32) {
+        let regs = self.regs();
+        regs.ioregsel.set(index);
+        regs.iowin.set(val);
+    }
+
+    /// Bring up the IO APIC described by an MADT type-1 entry, masking
+    /// every redirection entry until a handler is registered for it.
+    ///
+    /// Only the first IO APIC the MADT advertises is ever brought up (see
+    /// the caller in `mach::pc::initialise`); multi-IO-APIC systems route
+    /// the GSIs owned by every later one nowhere; there's no fallback
+    /// other than those interrupts never firing.
+    pub fn init(info: &IoApicInfo) -> IoApic {
+        let mut apic = IoApic{
+            base: info.address,
+            gsi_base: info.gsi_base,
+            num_entries: 0,
+            handlers: Vec::new(),
+        };
+
+        let ver = apic.read_reg(IOAPICVER);
+        apic.num_entries = ((ver >> 16) & 0xFF) + 1;
+        apic.handlers.grow(apic.num_entries as uint, &None);
+
+        for i in range(0u32, apic.num_entries) {
+            apic.write_reg(IOREDTBL + i * 2, redtbl::mask::set(0, redtbl::mask::masked));
+            apic.write_reg(IOREDTBL + i * 2 + 1, 0);
+        }
+
+        apic
+    }
+
+    /// Whether this IO APIC owns the given global system interrupt.
+    pub fn owns_gsi(&self, gsi: u32) -> bool {
+        gsi >= self.gsi_base && gsi < self.gsi_base + self.num_entries
+    }
+
+    fn redirection_entry(&self, vector: u8, level_trigger: bool, active_low: bool) -> u32 {
+        let mut entry = redtbl::vector::set(0, vector as u32);
+        if level_trigger {
+            entry = redtbl::trigger_mode::set(entry, redtbl::trigger_mode::level);
+        }
+        if active_low {
+            entry = redtbl::polarity::set(entry, redtbl::polarity::active_low);
+        }
+        entry
+    }
+
+    /// Register a handler for the given global system interrupt, mirroring
+    /// `Pic::register`'s signature so the two controllers are interchangeable
+    /// from `MachineState::register_irq`.
+    pub fn register(&mut self, irq: uint, f: Rc<RefCell<Box<IrqHandler>>>, level_trigger: bool) {
+        let local = irq - self.gsi_base as uint;
+        *self.handlers.get_mut(local) = Some(f);
+
+        // Vector 0x20 + irq matches the legacy PIC remapping so the rest of
+        // the trap dispatch table does not need to know which controller is
+        // in use.
+        let vector = (0x20 + irq) as u8;
+        let entry = self.redirection_entry(vector, level_trigger, false);
+        self.write_reg(IOREDTBL + local as u32 * 2, redtbl::mask::set(entry, redtbl::mask::masked));
+    }
+
+    pub fn enable(&self, irq: uint) {
+        let local = irq - self.gsi_base as uint;
+        let entry = self.read_reg(IOREDTBL + local as u32 * 2);
+        self.write_reg(IOREDTBL + local as u32 * 2, redtbl::mask::set(entry, redtbl::mask::unmasked));
+    }
+
+    pub fn disable(&self, irq: uint) {
+        let local = irq - self.gsi_base as uint;
+        let entry = self.read_reg(IOREDTBL + local as u32 * 2);
+        self.write_reg(IOREDTBL + local as u32 * 2, redtbl::mask::set(entry, redtbl::mask::masked));
+    }
+}
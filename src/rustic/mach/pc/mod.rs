@@ -20,7 +20,10 @@ use std::default::Default;
 use std::rc::Rc;
 
 use mach::{IrqHandler, Machine, MachineState, TimerHandlers, Keyboard, IoPort, Serial, Mmio, parity};
+use mach::driver::{Driver, DeviceDescriptor, DeviceManager, DriverError};
 
+mod acpi;
+mod ioapic;
 mod kb;
 mod pic;
 mod pit;
@@ -29,44 +32,140 @@ mod vga;
 
 pub struct State {
     irq_ctlr: pic::Pic,
+    // Present once the MADT advertises an IO APIC; when set, it is used in
+    // place of `irq_ctlr` and the 8259 is left fully masked.
+    ioapic: Option<ioapic::IoApic>,
+    irq_overrides: Vec<acpi::InterruptOverride>,
     timer: pit::Pit,
     keyboard: kb::PS2Keyboard,
     screen: vga::Vga,
+    devices: DeviceManager,
     timer_handlers: Vec<extern "Rust" fn(uint)>,
 }
 
 impl State {
     pub fn new() -> State {
         State{irq_ctlr: pic::Pic::new(),
+              ioapic: None,
+              irq_overrides: Vec::new(),
               timer: pit::Pit::new(),
               keyboard: kb::PS2Keyboard::new(),
               screen: vga::Vga::new(),
+              devices: DeviceManager::new(),
               timer_handlers: Vec::with_capacity(16)}
     }
 }
 
+// The legacy PC platform has no real bus enumeration for these; their
+// resources are fixed by convention rather than discovered, but routing
+// them through the same descriptor shape keeps `initialise` uniform.
+static KEYBOARD_DESCRIPTOR: DeviceDescriptor = DeviceDescriptor{
+    name: "ps2kbd", io_ports: Some((0x60, 5)), mmio: None, irq: Some(1),
+};
+static VGA_DESCRIPTOR: DeviceDescriptor = DeviceDescriptor{
+    name: "vga", io_ports: None, mmio: Some((0xB8000, 0x8000)), irq: None,
+};
+
+impl Driver for kb::PS2Keyboard {
+    fn name(&self) -> &'static str { "ps2kbd" }
+
+    fn probe(&self, desc: &DeviceDescriptor) -> bool {
+        desc.io_ports == Some((0x60, 5))
+    }
+
+    fn attach(&mut self, _desc: &DeviceDescriptor) -> Result<Box<Driver>, DriverError> {
+        // `attach` only needs to prove the claim succeeds; there is no way
+        // in this Rust to hand the caller back a usable, concretely-typed
+        // `PS2Keyboard` through a `Box<Driver>` (no downcasting, no trait
+        // object upcasting), so the real hardware-initialising
+        // `PS2Keyboard::init()` still happens exactly once below, gated on
+        // `is_bound`. Calling it here too would bring the controller up
+        // twice.
+        Ok(box kb::PS2Keyboard::new() as Box<Driver>)
+    }
+}
+
+impl Driver for vga::Vga {
+    fn name(&self) -> &'static str { "vga" }
+
+    fn probe(&self, desc: &DeviceDescriptor) -> bool {
+        desc.mmio == Some((0xB8000, 0x8000))
+    }
+
+    fn attach(&mut self, _desc: &DeviceDescriptor) -> Result<Box<Driver>, DriverError> {
+        // See the comment on PS2Keyboard's attach: the real `init()` runs
+        // once below, gated on `is_bound`, not here.
+        Ok(box vga::Vga::new() as Box<Driver>)
+    }
+}
+
 impl Machine for MachineState {
     fn initialise(&mut self) -> bool {
-        // Configure serial port.
+        // Configure serial port. Serial is exposed through the `Serial`
+        // trait as plain methods on `MachineState` (there is no standalone
+        // `Serial` struct instance anywhere), so there is no concrete type
+        // for a `Driver` impl to wrap and nothing for the device manager
+        // below to probe/attach against -- it stays on its own fixed
+        // configuration call rather than being routed through
+        // `DeviceManager`.
         self.serial_config(115200, 8, parity::NoParity, 1);
 
-        // Bring up the PIC.
+        // Bring up the PIC. Even when an IO APIC takes over routing below,
+        // the 8259 still needs to be programmed so it can be fully masked
+        // rather than left in its power-on state firing spurious vectors.
         self.state.irq_ctlr = pic::Pic::init();
 
+        // Prefer the Local APIC / IO APIC described by the MADT over the
+        // legacy 8259 when one is present.
+        match acpi::find_madt() {
+            Some(madt) => {
+                // Only the first IO APIC is ever brought up; see the doc
+                // comment on `IoApic::init` for what that leaves unrouted
+                // on multi-IO-APIC systems.
+                if let Some(info) = madt.io_apics.iter().next() {
+                    self.state.ioapic = Some(ioapic::IoApic::init(info));
+                    self.state.irq_overrides = madt.overrides;
+                    for irq in range(0u, 16) {
+                        self.state.irq_ctlr.disable(irq);
+                    }
+                }
+            }
+            None => (),
+        }
+
         // Bring up the PIT at 100hz.
         self.state.timer = pit::Pit::init(100);
 
+        // Register the built-in drivers against the fixed descriptors the
+        // PC platform exposes, then let the device manager decide what is
+        // actually present. `enumerate_and_bind` only settles *whether* a
+        // device is present (`is_bound` below) -- it does not hand back a
+        // concretely-typed, usable instance, so the real construction
+        // below is still the one real place each device is brought up.
+        // New hardware support needs both a driver and descriptor
+        // registered here and its own `is_bound`-gated construction below.
+        self.state.devices.register_driver(box kb::PS2Keyboard::new() as Box<Driver>);
+        self.state.devices.register_driver(box vga::Vga::new() as Box<Driver>);
+        self.state.devices.add_descriptor(KEYBOARD_DESCRIPTOR);
+        self.state.devices.add_descriptor(VGA_DESCRIPTOR);
+        self.state.devices.enumerate_and_bind();
+
         // Bring up the keyboard.
-        self.state.keyboard = kb::PS2Keyboard::init();
+        if self.state.devices.is_bound("ps2kbd") {
+            self.state.keyboard = kb::PS2Keyboard::init();
 
-        // Register the PIT and keyboard IRQs.
+            let keyboard_irq = Rc::new(RefCell::new(box self.state.keyboard as Box<IrqHandler>));
+            self.register_irq(kb::PS2Keyboard::irq_num(), keyboard_irq, true);
+        }
+
+        // Register the PIT IRQ.
         let timer_irq = Rc::new(RefCell::new(box self.state.timer as Box<IrqHandler>));
-        let keyboard_irq = Rc::new(RefCell::new(box self.state.keyboard as Box<IrqHandler>));
         self.register_irq(pit::Pit::irq_num(), timer_irq, true);
-        self.register_irq(kb::PS2Keyboard::irq_num(), keyboard_irq, true);
 
         // Set up the VGA screen.
-        self.state.screen.init();
+        if self.state.devices.is_bound("vga") {
+            self.state.screen.init();
+        }
 
         self.initialised = true;
 
@@ -74,6 +173,22 @@ impl Machine for MachineState {
     }
 
     fn register_irq(&mut self, irq: uint, f: Rc<RefCell<Box<IrqHandler>>>, level_trigger: bool) {
+        // An interrupt-source-override remaps a legacy ISA IRQ (e.g. the
+        // PIT on IRQ0) onto a different global system interrupt.
+        let gsi = self.state.irq_overrides.iter()
+            .find(|o| o.source_irq as uint == irq)
+            .map(|o| o.gsi as uint)
+            .unwrap_or(irq);
+
+        match self.state.ioapic {
+            Some(ref mut ioapic) if ioapic.owns_gsi(gsi as u32) => {
+                ioapic.register(gsi, f, level_trigger);
+                ioapic.enable(gsi);
+                return;
+            }
+            _ => (),
+        }
+
         self.state.irq_ctlr.register(irq, f, level_trigger);
         self.state.irq_ctlr.enable(irq);
     }
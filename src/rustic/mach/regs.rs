@@ -0,0 +1,134 @@
+/*
+ * Copyright (c) 2014 Matthew Iselin
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+//! Type-safe volatile MMIO registers, modeled on tock's register crate.
+//!
+//! `Mmio::mmio_read`/`mmio_write` are a bare `*const`/`*mut` cast with no
+//! guarantee the access is actually volatile and no connection to a
+//! device's register layout, which is why every driver ends up hand
+//! rolling offsets. The wrapper types here (`ReadOnly`, `WriteOnly`,
+//! `ReadWrite`) are meant to be laid out in a `#[repr(C)]` struct matching
+//! a device's register block and mapped straight over its MMIO base, e.g.
+//!
+//! ```ignore
+//! #[repr(C)]
+//! struct IoApicRegs {
+//!     ioregsel: ReadWrite<u32>,
+//!     _pad: [u32, ..3],
+//!     iowin: ReadWrite<u32>,
+//! }
+//! ```
+//!
+//! `Mmio` itself is kept around as a thin fallback for one-off accesses
+//! that do not warrant a whole register struct.
+
+use std::ptr;
+
+/// A register that may only be read.
+pub struct ReadOnly<T> {
+    value: T,
+}
+
+impl<T> ReadOnly<T> {
+    pub fn get(&self) -> T {
+        unsafe { ptr::read_volatile(&self.value) }
+    }
+}
+
+/// A register that may only be written.
+pub struct WriteOnly<T> {
+    value: T,
+}
+
+impl<T> WriteOnly<T> {
+    pub fn set(&mut self, val: T) {
+        unsafe { ptr::write_volatile(&mut self.value, val) };
+    }
+}
+
+/// A register that may be both read and written.
+pub struct ReadWrite<T> {
+    value: T,
+}
+
+impl<T> ReadWrite<T> {
+    pub fn get(&self) -> T {
+        unsafe { ptr::read_volatile(&self.value) }
+    }
+
+    pub fn set(&mut self, val: T) {
+        unsafe { ptr::write_volatile(&mut self.value, val) };
+    }
+}
+
+/// Declares a bitfield-typed getter/setter pair for a register field,
+/// plus named constants for its enumerated values where given. Keeps the
+/// shift/mask arithmetic for a device's fields in one place instead of
+/// scattered across the driver that uses them.
+///
+/// ```ignore
+/// bitfield!(pit_mode: u8 {
+///     bcd: 0..0,
+///     mode: 1..3 => [ rate_generator = 2, square_wave = 3 ],
+///     access: 4..5 => [ lobyte_hibyte = 3 ],
+///     channel: 6..7,
+/// })
+/// ```
+macro_rules! bitfield(
+    ($reg:ident : $bits:ty { $($field:ident : $lo:expr .. $hi:expr
+        $(=> [ $($name:ident = $val:expr),+ ])* ),+ $(,)* }) => (
+        #[allow(non_snake_case)]
+        pub mod $reg {
+            pub type Bits = $bits;
+
+            $(
+                pub mod $field {
+                    use super::Bits;
+
+                    pub static SHIFT: uint = $lo;
+                    pub static MASK: Bits = (((1 as Bits) << ($hi - $lo + 1)) - 1) << $lo;
+
+                    pub fn get(reg: Bits) -> Bits {
+                        (reg & MASK) >> SHIFT
+                    }
+
+                    pub fn set(reg: Bits, val: Bits) -> Bits {
+                        (reg & !MASK) | ((val << SHIFT) & MASK)
+                    }
+
+                    $($(
+                        pub static $name: Bits = $val;
+                    )+)*
+                }
+            )+
+        }
+    );
+)
+
+/// Layout of an IO APIC redirection table entry's low dword (see
+/// `mach::pc::ioapic`).
+bitfield!(redtbl: u32 {
+    vector: 0..7,
+    delivery_mode: 8..10 => [
+        fixed = 0, lowest_priority = 1, smi = 2, nmi = 4, init = 5, extint = 7
+    ],
+    dest_mode: 11..11,
+    delivery_status: 12..12,
+    polarity: 13..13 => [active_high = 0, active_low = 1],
+    remote_irr: 14..14,
+    trigger_mode: 15..15 => [edge = 0, level = 1],
+    mask: 16..16 => [unmasked = 0, masked = 1],
+})